@@ -3,13 +3,13 @@ use chrono::{Date, Local, Utc};
 use colored::Colorize;
 use regex::Regex;
 use std::fmt::Debug;
-use std::path::PathBuf;
-use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{
     env,
     fs::{self, DirEntry, ReadDir},
     os,
-    time::SystemTime,
+    time::{Instant, SystemTime},
 };
 fn print<T: std::fmt::Display>(s: T) {
     println!("{}", s);
@@ -20,7 +20,7 @@ enum FSElement {
     Directory(Vec<FSElement>,String),
     File {
         name: String,
-        path: Rc<PathBuf>,
+        path: Arc<PathBuf>,
         is_md: bool,
     },
 }
@@ -96,7 +96,114 @@ impl FSElement {
     }
 }
 
-fn index_filesystem(dir: ReadDir, forbidden_paths: &Vec<String>, root_fs: &mut FSElement) {
+#[derive(Debug)]
+struct IgnoreRule {
+    regex: Regex,
+    negate: bool,
+    dir_only: bool,
+}
+
+fn gitignore_glob_to_regex(pattern: &str, anchored: bool) -> String {
+    let mut re = String::from("^");
+    if !anchored {
+        re.push_str("(?:.*/)?");
+    }
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                if i + 1 < chars.len() && chars[i + 1] == '*' {
+                    if i + 2 < chars.len() && chars[i + 2] == '/' {
+                        re.push_str("(?:.*/)?");
+                        i += 3;
+                    } else {
+                        re.push_str(".*");
+                        i += 2;
+                    }
+                    continue;
+                }
+                re.push_str("[^/]*");
+            }
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                let mut j = i + 1;
+                let mut class = String::from("[");
+                if j < chars.len() && (chars[j] == '!' || chars[j] == '^') {
+                    class.push('^');
+                    j += 1;
+                }
+                while j < chars.len() && chars[j] != ']' {
+                    class.push(chars[j]);
+                    j += 1;
+                }
+                class.push(']');
+                re.push_str(&class);
+                i = j;
+            }
+            c if "\\.+()|^$".contains(c) => {
+                re.push('\\');
+                re.push(c);
+            }
+            c => re.push(c),
+        }
+        i += 1;
+    }
+    re.push('$');
+    re
+}
+
+fn parse_ignore_rules(gitignore: &str) -> Vec<IgnoreRule> {
+    gitignore
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let mut pattern = line;
+            let negate = pattern.starts_with('!');
+            if negate {
+                pattern = &pattern[1..];
+            }
+            if pattern.is_empty() {
+                return None;
+            }
+            let dir_only = pattern.ends_with('/') && pattern.len() > 1;
+            if dir_only {
+                pattern = &pattern[..pattern.len() - 1];
+            }
+            if pattern.is_empty() {
+                return None;
+            }
+            let anchored = pattern.starts_with('/') || pattern.get(1..).is_some_and(|p| p.contains('/'));
+            if pattern.starts_with('/') {
+                pattern = &pattern[1..];
+            }
+            let regex_str = gitignore_glob_to_regex(pattern, anchored);
+            Regex::new(&regex_str).ok().map(|regex| IgnoreRule {
+                regex,
+                negate,
+                dir_only,
+            })
+        })
+        .collect()
+}
+
+fn is_ignored(rel_path: &str, is_dir: bool, rules: &[IgnoreRule]) -> bool {
+    let mut ignored = false;
+    for rule in rules {
+        if rule.dir_only && !is_dir {
+            continue;
+        }
+        if rule.regex.is_match(rel_path) {
+            ignored = !rule.negate;
+        }
+    }
+    ignored
+}
+
+fn index_filesystem(dir: ReadDir, rel_prefix: &str, ignore_rules: &[IgnoreRule], root_fs: &mut FSElement) {
     let root_vec;
     if let FSElement::Directory(v,name) = root_fs{
         root_vec = v
@@ -111,10 +218,15 @@ fn index_filesystem(dir: ReadDir, forbidden_paths: &Vec<String>, root_fs: &mut F
         let path = path.unwrap();
         let name = path.file_name();
         let name = name.to_str().unwrap();
-        if forbidden_paths.iter().find(|e| e.contains(name)).is_some() {
+        let t = path.file_type().unwrap();
+        let rel_path = if rel_prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}/{}", rel_prefix, name)
+        };
+        if is_ignored(&rel_path, t.is_dir(), ignore_rules) {
             continue;
         }
-        let t = path.file_type().unwrap();
         if t.is_dir() {
             let read_dir = fs::read_dir(path.path());
             if let Err(e) = read_dir {
@@ -123,7 +235,7 @@ fn index_filesystem(dir: ReadDir, forbidden_paths: &Vec<String>, root_fs: &mut F
             }
             let read_dir = read_dir.unwrap();
             let mut fs_directory = FSElement::Directory(Vec::with_capacity(10),name.to_owned());
-            index_filesystem(read_dir, &forbidden_paths, &mut fs_directory);
+            index_filesystem(read_dir, &rel_path, ignore_rules, &mut fs_directory);
             if let FSElement::Directory(d, name) = &mut fs_directory{
                 d.sort_by(|a,b|{
                     a.sort_value().cmp(&b.sort_value())
@@ -133,7 +245,7 @@ fn index_filesystem(dir: ReadDir, forbidden_paths: &Vec<String>, root_fs: &mut F
         } else {
             root_vec.push(FSElement::File {
                 name: name.to_owned(),
-                path: Rc::new(path.path()),
+                path: Arc::new(path.path()),
                 is_md: name.ends_with(".md"),
             });
         }
@@ -146,22 +258,107 @@ struct HeadLine<'a> {
     title: &'a str,
 }
 
+fn github_slug(title: &str) -> String {
+    let cleaned: String = title
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join("-")
+}
+
 impl HeadLine<'_> {
-    fn to_md(&self) -> String {
+    fn to_md(&self, seen_slugs: &mut std::collections::HashMap<String, usize>) -> String {
+        let slug = github_slug(self.title);
+        let count = seen_slugs.entry(slug.clone()).or_insert(0);
+        let anchor = if *count == 0 {
+            slug
+        } else {
+            format!("{}-{}", slug, count)
+        };
+        *count += 1;
+
         return format!(
             "{}- [{}](#{})  \n",
             "    ".repeat((self.intend - 1).into()),
             self.title,
-            self.title.to_ascii_lowercase().replace(" ", "-")
+            anchor
         );
     }
 }
 
-fn process_md(path: PathBuf, name: &str) {
+#[derive(Debug, Clone, Copy, Default)]
+struct MdOptions {
+    dry_run: bool,
+    backup: bool,
+    verbose: bool,
+}
+
+#[derive(Debug)]
+enum MdOutcome {
+    Updated,
+    Unchanged,
+    DryRun,
+    Error(String),
+}
+
+fn print_md_outcome(name: &str, outcome: &MdOutcome, opts: &MdOptions) {
+    match outcome {
+        MdOutcome::Updated => println!("{} updated sucessfully!", name.green()),
+        MdOutcome::DryRun => println!("{} would be updated (dry-run)", name.yellow()),
+        MdOutcome::Unchanged => {
+            if opts.verbose {
+                println!("{} unchanged", name);
+            }
+        }
+        MdOutcome::Error(e) => println!("ERROR updating {} - {}", name.red(), e.red()),
+    }
+}
+
+fn print_region_diff(name: &str, old: &str, new: &str) {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let max_common = old_lines.len().min(new_lines.len());
+    let mut prefix = 0;
+    while prefix < max_common && old_lines[prefix] == new_lines[prefix] {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < max_common - prefix
+        && old_lines[old_lines.len() - 1 - suffix] == new_lines[new_lines.len() - 1 - suffix]
+    {
+        suffix += 1;
+    }
+
+    let old_changed = &old_lines[prefix..old_lines.len() - suffix];
+    let new_changed = &new_lines[prefix..new_lines.len() - suffix];
+
+    println!("--- {} (dry-run)", name);
+    println!(
+        "@@ -{},{} +{},{} @@",
+        prefix + 1,
+        old_changed.len(),
+        prefix + 1,
+        new_changed.len()
+    );
+    for line in old_changed {
+        println!("{}", format!("-{}", line).red());
+    }
+    for line in new_changed {
+        println!("{}", format!("+{}", line).green());
+    }
+}
+
+fn write_backup(path: &Path, content: &str) -> std::io::Result<()> {
+    let backup_path = PathBuf::from(format!("{}.bak", path.display()));
+    fs::write(backup_path, content)
+}
+
+fn process_md(path: PathBuf, name: &str, opts: &MdOptions) -> MdOutcome {
     let content = fs::read_to_string(path.clone());
     if let Err(e) = content {
-        println!("{}", e);
-        return;
+        return MdOutcome::Error(e.to_string());
     }
     let mut content = content.unwrap();
 
@@ -193,9 +390,10 @@ fn process_md(path: PathBuf, name: &str) {
     let mut result = String::with_capacity(head_lines.len() * 20);
     result.push_str(TOC_BEGIN_PREFIX);
     result.push('\n');
+    let mut seen_slugs = std::collections::HashMap::new();
     head_lines
         .iter()
-        .for_each(|h| result.push_str(h.to_md().as_str()));
+        .for_each(|h| result.push_str(h.to_md(&mut seen_slugs).as_str()));
 
     result.push_str(
         format!(
@@ -206,22 +404,90 @@ fn process_md(path: PathBuf, name: &str) {
     );
     result.push_str(TOC_END_PREFIX);
 
+    let re = Regex::new(&format!(r"{}([\S\s]*?){}", TOC_BEGIN_PREFIX, TOC_END_PREFIX)).unwrap();
+    let old_region = if content.contains(TOC_FIRST_PREFIX) {
+        TOC_FIRST_PREFIX.to_owned()
+    } else {
+        re.find(&content).map(|m| m.as_str().to_owned()).unwrap_or_default()
+    };
+
     let rep;
     if content.contains(TOC_FIRST_PREFIX) {
         rep = content.replace(TOC_FIRST_PREFIX, &result.as_str());
     } else {
-        let re_str = format!(r"{}([\S\s]*?){}", TOC_BEGIN_PREFIX, TOC_END_PREFIX);
-        let re: Regex = Regex::new(re_str.as_str()).unwrap();
         rep = re.replace(&content, result.as_str()).to_string();
     }
-    if rep != content {
-        let res = fs::write(path.clone(), rep);
-        if let Err(e) = res {
-            println!("ERROR updating {} - {}", name.red(), e.to_string().red());
-        } else {
-            println!("{} updated sucessfully!", name.green());
+    if rep == content {
+        return MdOutcome::Unchanged;
+    }
+    if opts.dry_run {
+        print_region_diff(name, &old_region, &result);
+        return MdOutcome::DryRun;
+    }
+    if opts.backup {
+        if let Err(e) = write_backup(&path, &content) {
+            return MdOutcome::Error(format!("backup failed: {}", e));
+        }
+    }
+    match fs::write(path.clone(), rep) {
+        Err(e) => MdOutcome::Error(e.to_string()),
+        Ok(_) => MdOutcome::Updated,
+    }
+}
+
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn process_markdowns_parallel(files: Vec<FSElement>, threads: usize, opts: MdOptions) -> bool {
+    let (tx, rx) = crossbeam_channel::unbounded::<FSElement>();
+    for file in files {
+        tx.send(file).unwrap();
+    }
+    drop(tx);
+
+    let mut handles = Vec::with_capacity(threads);
+    for _ in 0..threads.max(1) {
+        let rx = rx.clone();
+        handles.push(std::thread::spawn(move || {
+            let mut results = Vec::new();
+            while let Ok(file) = rx.recv() {
+                if let FSElement::File { name, path, is_md: _ } = file {
+                    let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        process_md((*path).to_path_buf(), name.as_str(), &opts)
+                    }))
+                    .unwrap_or_else(|e| MdOutcome::Error(format!("panicked: {}", panic_message(&e))));
+                    results.push((name, outcome));
+                }
+            }
+            results
+        }));
+    }
+
+    let mut any_failed = false;
+    for handle in handles {
+        match handle.join() {
+            Ok(results) => {
+                for (name, outcome) in results {
+                    if matches!(outcome, MdOutcome::Error(_)) {
+                        any_failed = true;
+                    }
+                    print_md_outcome(&name, &outcome, &opts);
+                }
+            }
+            Err(_) => {
+                any_failed = true;
+                println!("{}", "ERROR: a worker thread panicked".red());
+            }
         }
     }
+    any_failed
 }
 const TOC_FIRST_PREFIX: &str = "<!--%toc%-->";
 const TOC_BEGIN_PREFIX: &str = "<!--%table_of_contents_begin%-->";
@@ -230,77 +496,285 @@ const TOC_END_PREFIX: &str = "<!--%table_of_contents_end%-->";
 const GFS_FIRST_PREFIX: &str = "<!--%gfs%-->";
 const GFS_BEGIN_PREFIX: &str = "<!--%file_structure_begin%-->";
 const GFS_END_PREFIX: &str = "<!--%file_structure_end%-->";
-fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
 
-    let dir = fs::read_dir("./").unwrap();
-    let gitignore = fs::read_to_string("./.gitignore");
+#[derive(Clone, Copy)]
+enum WatchCommand {
+    Toc,
+    Fs(bool),
+}
 
-    let mut forbidden_paths = if let Ok(s) = gitignore {
-        let lines = s.lines();
-        lines.map(String::from).collect()
-    } else {
-        Vec::new()
-    };
-    forbidden_paths.push(".git".to_owned());
-
-
-    let mut indexed_fs = FSElement::Directory(Vec::new(),"./".to_owned());
-    index_filesystem(dir, &forbidden_paths, &mut indexed_fs);
-    let mut args_iter = args.iter();
-    let key = args_iter.next();
-    
-    if let Some(mut k) = key {
-        let k = k.to_ascii_lowercase();
-        match k.as_str() {
-            "gtoc" => {
-                indexed_fs.get_markdowns().iter().for_each(|m|{
-                    if let FSElement::File { name, path, is_md: _ } = m{
-                        process_md((*path).to_path_buf(), name.as_str())
-                    }
-                })
-                // process_dir(dir, &forbidden_paths)
-            }
-            "gfs" => {
-                let arg1 = args_iter.next();
-                let mut dir_only = false;
-                if let Some(v) = arg1{
-                    if v.contains("--dironly"){
-                        println!("dironly=true");
-                        dir_only = true;
-                    }
-                }
-                process_md_fs(&indexed_fs, dir_only)
+const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+
+fn collect_file_paths<'a>(e: &'a FSElement, out: &mut Vec<&'a PathBuf>) {
+    match e {
+        FSElement::Directory(v, _) => v.iter().for_each(|c| collect_file_paths(c, out)),
+        FSElement::File { path, .. } => out.push(path),
+    }
+}
+
+fn subtree_touched(e: &FSElement, touched: &std::collections::HashSet<PathBuf>) -> bool {
+    let mut paths = Vec::new();
+    collect_file_paths(e, &mut paths);
+    paths.iter().any(|p| touched.contains(p.as_path()))
+}
+
+fn snapshot_mtimes(e: &FSElement, out: &mut std::collections::HashMap<PathBuf, SystemTime>) {
+    match e {
+        FSElement::Directory(v, _) => v.iter().for_each(|c| snapshot_mtimes(c, out)),
+        FSElement::File { path, .. } => {
+            if let Ok(modified) = fs::metadata(&**path).and_then(|m| m.modified()) {
+                out.insert((**path).clone(), modified);
             }
-            _ => {
-                println!("{}Unknown arg '{}'", "[ERROR]: ".bold().red(), k.blue())
+        }
+    }
+}
+
+fn run_watch(
+    root: &Path,
+    ignore_rules: &[IgnoreRule],
+    command: WatchCommand,
+    opts: &MdOptions,
+    target_regexes: &[Regex],
+) {
+    println!("{}", "Watching for changes (Ctrl+C to stop)...".blue());
+
+    let mut last_snapshot: std::collections::HashMap<PathBuf, SystemTime> =
+        std::collections::HashMap::new();
+    let mut buffered_events: Vec<PathBuf> = Vec::new();
+    let mut last_event_at: Option<Instant> = None;
+
+    loop {
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+
+        let dir = match fs::read_dir(root) {
+            Ok(d) => d,
+            Err(e) => {
+                print(e);
+                continue;
             }
         };
+        let mut indexed_fs = FSElement::Directory(Vec::new(), root.to_string_lossy().into_owned());
+        index_filesystem(dir, "", ignore_rules, &mut indexed_fs);
+
+        let mut snapshot = std::collections::HashMap::new();
+        snapshot_mtimes(&indexed_fs, &mut snapshot);
+
+        let changed: Vec<PathBuf> = snapshot
+            .iter()
+            .filter(|(path, modified)| last_snapshot.get(*path) != Some(*modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        if !changed.is_empty() {
+            buffered_events.extend(changed);
+            last_event_at = Some(Instant::now());
+        }
+        last_snapshot = snapshot;
+
+        let quiet_long_enough = last_event_at
+            .map(|t| t.elapsed() >= WATCH_DEBOUNCE)
+            .unwrap_or(false);
+        if buffered_events.is_empty() || !quiet_long_enough {
+            continue;
+        }
+        let touched: std::collections::HashSet<PathBuf> = buffered_events.drain(..).collect();
+        last_event_at = None;
+
+        match command {
+            WatchCommand::Toc => {
+                indexed_fs.get_markdowns().iter().for_each(|m| {
+                    if let FSElement::File { name, path, is_md: _ } = m {
+                        if touched.contains(&**path) && matches_any_target_or_unrestricted(m, root, target_regexes) {
+                            let outcome = process_md((**path).to_path_buf(), name.as_str(), opts);
+                            print_md_outcome(name, &outcome, opts);
+                        }
+                    }
+                });
+            }
+            WatchCommand::Fs(dir_only) => {
+                process_md_fs(&indexed_fs, dir_only, opts, root, target_regexes, Some(&touched));
+            }
+        }
+    }
+}
+
+fn expand_path(raw: &str) -> PathBuf {
+    if raw == "~" {
+        if let Ok(home) = env::var("HOME") {
+            return PathBuf::from(home);
+        }
+    } else if let Some(rest) = raw.strip_prefix("~/") {
+        if let Ok(home) = env::var("HOME") {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    PathBuf::from(raw)
+}
+
+fn filter_markdown_targets(files: Vec<FSElement>, root: &Path, target_regexes: &[Regex]) -> Vec<FSElement> {
+    if target_regexes.is_empty() {
+        return files;
+    }
+    files
+        .into_iter()
+        .filter(|f| matches_any_target(f, root, target_regexes))
+        .collect()
+}
+
+fn matches_any_target(file: &FSElement, root: &Path, target_regexes: &[Regex]) -> bool {
+    if let FSElement::File { path, .. } = file {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        let path_str = rel.to_string_lossy().replace('\\', "/");
+        target_regexes.iter().any(|re| re.is_match(&path_str))
     } else {
+        false
+    }
+}
+
+fn matches_any_target_or_unrestricted(file: &FSElement, root: &Path, target_regexes: &[Regex]) -> bool {
+    target_regexes.is_empty() || matches_any_target(file, root, target_regexes)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.is_empty() {
         println!(
             "
 {}
 {}
 [COMMANDS]
-gtoc            | Embed '{}' in your markdown document to generate a table of content
-gfs [--dironly] | Embed '{}' in your markdown doc to generate a view of subdirectories
+gtoc [path/glob...] [--watch] [--threads=N] [--dry-run] [--backup] [--verbose] | Embed '{}' in your markdown document to generate a table of content
+gfs [path/glob...] [--dironly] [--watch] [--dry-run] [--backup] [--verbose]    | Embed '{}' in your markdown doc to generate a view of subdirectories
 
 ",
             "===MarkdownUtils===".bold().green(),
             "by Jadr".blue(),
             TOC_FIRST_PREFIX.blue(),
             GFS_FIRST_PREFIX.blue()
-        )
+        );
+        return;
+    }
+
+    let threads = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--threads="))
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+
+    let opts = MdOptions {
+        dry_run: args.iter().any(|a| a == "--dry-run"),
+        backup: args.iter().any(|a| a == "--backup"),
+        verbose: args.iter().any(|a| a == "--verbose"),
+    };
+
+    let k = args[0].to_ascii_lowercase();
+    let rest = &args[1..];
+
+    let mut root: Option<PathBuf> = None;
+    let mut globs: Vec<String> = Vec::new();
+    for a in rest.iter().filter(|a| !a.starts_with("--")) {
+        let resolved = expand_path(a);
+        if resolved.is_dir() {
+            if root.is_none() {
+                root = Some(resolved);
+            } else {
+                println!(
+                    "{}Ignoring extra root '{}', only one indexing root is supported",
+                    "[WARN]: ".bold().yellow(),
+                    resolved.display()
+                );
+            }
+        } else {
+            globs.push(resolved.to_string_lossy().replace('\\', "/"));
+        }
     }
+    let root = root.unwrap_or_else(|| PathBuf::from("./"));
+    let target_regexes: Vec<Regex> = globs
+        .iter()
+        .map(|g| Regex::new(&gitignore_glob_to_regex(g, true)).unwrap())
+        .collect();
+
+    let dir = match fs::read_dir(&root) {
+        Ok(d) => d,
+        Err(e) => {
+            println!("{}Cannot read '{}' - {}", "[ERROR]: ".bold().red(), root.display(), e);
+            return;
+        }
+    };
+    let mut gitignore = fs::read_to_string(root.join(".gitignore")).unwrap_or_default();
+    gitignore.push_str("\n.git/\n");
+    let ignore_rules = parse_ignore_rules(&gitignore);
+
+    let mut indexed_fs = FSElement::Directory(Vec::new(), root.to_string_lossy().into_owned());
+    index_filesystem(dir, "", &ignore_rules, &mut indexed_fs);
+
+    match k.as_str() {
+        "gtoc" => {
+            if rest.iter().any(|a| a == "--watch") {
+                run_watch(&root, &ignore_rules, WatchCommand::Toc, &opts, &target_regexes);
+            } else {
+                let files = filter_markdown_targets(indexed_fs.get_markdowns(), &root, &target_regexes);
+                if process_markdowns_parallel(files, threads, opts) {
+                    std::process::exit(1);
+                }
+            }
+        }
+        "gfs" => {
+            let mut dir_only = false;
+            let mut watch = false;
+            for v in rest {
+                if v.contains("--dironly"){
+                    println!("dironly=true");
+                    dir_only = true;
+                }
+                if v == "--watch" {
+                    watch = true;
+                }
+            }
+            if watch {
+                run_watch(&root, &ignore_rules, WatchCommand::Fs(dir_only), &opts, &target_regexes);
+            } else {
+                process_md_fs(&indexed_fs, dir_only, &opts, &root, &target_regexes, None)
+            }
+        }
+        _ => {
+            println!("{}Unknown arg '{}'", "[ERROR]: ".bold().red(), k.blue())
+        }
+    };
 }
 
-fn process_md_fs(e: &FSElement, dir_only: bool){
-    fn process_md(root: &FSElement, mdfile: &FSElement, dir_only: bool){
+fn process_md_fs(
+    e: &FSElement,
+    dir_only: bool,
+    opts: &MdOptions,
+    index_root: &Path,
+    target_regexes: &[Regex],
+    touched: Option<&std::collections::HashSet<PathBuf>>,
+){
+    fn process_md(
+        root: &FSElement,
+        mdfile: &FSElement,
+        dir_only: bool,
+        opts: &MdOptions,
+        index_root: &Path,
+        target_regexes: &[Regex],
+        touched: Option<&std::collections::HashSet<PathBuf>>,
+    ){
+        if !matches_any_target_or_unrestricted(mdfile, index_root, target_regexes) {
+            return;
+        }
+        if let Some(touched) = touched {
+            if !subtree_touched(root, touched) {
+                return;
+            }
+        }
         if let FSElement::File { name, path, is_md} = mdfile{
-            
+
             let content = fs::read_to_string(&**path);
             if let Err(e) = content {
-                println!("Err{}", e);
+                print_md_outcome(name, &MdOutcome::Error(e.to_string()), opts);
                 return;
             }
 
@@ -311,30 +785,45 @@ fn process_md_fs(e: &FSElement, dir_only: bool){
                 s.push_str(md.as_str());
                 s.push('\n');
                 s.push_str(GFS_END_PREFIX);
-                s.push('\n');
                 return s
             }
-            
-            
+
+
             let content = content.unwrap();
+            let re = Regex::new(&format!(r"{}([\S\s]*?){}", GFS_BEGIN_PREFIX, GFS_END_PREFIX)).unwrap();
+            let old_region = if content.contains(GFS_FIRST_PREFIX) {
+                GFS_FIRST_PREFIX.to_owned()
+            } else {
+                re.find(&content).map(|m| m.as_str().to_owned()).unwrap_or_default()
+            };
+            let new_region = pre_suffix(root.to_md(dir_only));
             let embed;
             if content.contains(GFS_FIRST_PREFIX){
-                embed = content.replace(GFS_FIRST_PREFIX, &pre_suffix(root.to_md(dir_only)));
+                embed = content.replace(GFS_FIRST_PREFIX, &new_region);
             } else {
-                let re_str = format!(r"{}([\S\s]*?){}", GFS_BEGIN_PREFIX, GFS_END_PREFIX);
-                let re: Regex = Regex::new(re_str.as_str()).unwrap();
-                embed = re.replace(&content, &pre_suffix(root.to_md(dir_only))).to_string();
+                embed = re.replace(&content, &new_region).to_string();
             }
 
-            if embed != content {
-                let res = fs::write((**path).clone(), embed);
-                if let Err(e) = res {
-                    println!("ERROR updating {} - {}", name.red(), e.to_string().red());
-                } else {
-                    println!("{} updated sucessfully!", name.green());
+            if embed == content {
+                print_md_outcome(name, &MdOutcome::Unchanged, opts);
+                return;
+            }
+            if opts.dry_run {
+                print_region_diff(name, &old_region, &new_region);
+                print_md_outcome(name, &MdOutcome::DryRun, opts);
+                return;
+            }
+            if opts.backup {
+                if let Err(e) = write_backup(path.as_path(), &content) {
+                    print_md_outcome(name, &MdOutcome::Error(format!("backup failed: {}", e)), opts);
+                    return;
                 }
             }
-            
+            let res = fs::write((**path).clone(), embed);
+            match res {
+                Err(e) => print_md_outcome(name, &MdOutcome::Error(e.to_string()), opts),
+                Ok(_) => print_md_outcome(name, &MdOutcome::Updated, opts),
+            }
         }
     }
 
@@ -343,10 +832,10 @@ fn process_md_fs(e: &FSElement, dir_only: bool){
             for element in vec{
                 if let FSElement::File { name:_, path: _, is_md} = element{
                     if *is_md{
-                        process_md(e, element,dir_only)
+                        process_md(e, element,dir_only, opts, index_root, target_regexes, touched)
                     }
                 }else{
-                    process_md_fs(element,dir_only);
+                    process_md_fs(element,dir_only, opts, index_root, target_regexes, touched);
                 }
             }
         },